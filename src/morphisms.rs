@@ -4,17 +4,63 @@
 //! - Objects in the CIM-ContextGraph category (nodes, edges, graphs)
 //! - Objects in the Bevy ECS category (entities, components, systems)
 
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 use cim_contextgraph::{NodeId, EdgeId, ContextGraphId as GraphId};
 use crate::events::*;
 
+/// Resource indexing domain ids to their visual entities.
+///
+/// Every morphism that maps a domain id (`NodeId`/`EdgeId`) to an ECS
+/// `Entity` consults this index instead of linear-scanning the
+/// `NodeVisual`/`EdgeVisual` queries. It is kept in sync by the spawn and
+/// despawn systems below and by [`StandardNodeMorphism`].
+#[derive(Resource, Default)]
+pub struct GraphIndex {
+    nodes: HashMap<NodeId, Entity>,
+    edges: HashMap<EdgeId, Entity>,
+}
+
+impl GraphIndex {
+    /// Record the entity spawned for a node id.
+    pub fn insert_node(&mut self, node_id: NodeId, entity: Entity) {
+        self.nodes.insert(node_id, entity);
+    }
+
+    /// Forget a node id, returning its entity if it was present.
+    pub fn remove_node(&mut self, node_id: &NodeId) -> Option<Entity> {
+        self.nodes.remove(node_id)
+    }
+
+    /// Look up the entity for a node id.
+    pub fn node_entity(&self, node_id: &NodeId) -> Option<Entity> {
+        self.nodes.get(node_id).copied()
+    }
+
+    /// Record the entity spawned for an edge id.
+    pub fn insert_edge(&mut self, edge_id: EdgeId, entity: Entity) {
+        self.edges.insert(edge_id, entity);
+    }
+
+    /// Forget an edge id, returning its entity if it was present.
+    pub fn remove_edge(&mut self, edge_id: &EdgeId) -> Option<Entity> {
+        self.edges.remove(edge_id)
+    }
+
+    /// Look up the entity for an edge id.
+    pub fn edge_entity(&self, edge_id: &EdgeId) -> Option<Entity> {
+        self.edges.get(edge_id).copied()
+    }
+}
+
 /// Morphism from domain node operations to visual node operations
 pub trait NodeMorphism {
     /// Map domain node creation to visual entity spawn
-    fn create_visual(&self, commands: &mut Commands, node_id: NodeId, graph_id: GraphId, position: Vec3) -> Entity;
+    fn create_visual(&self, commands: &mut Commands, index: &mut GraphIndex, node_id: NodeId, graph_id: GraphId, position: Vec3) -> Entity;
 
     /// Map domain node deletion to visual entity despawn
-    fn delete_visual(&self, commands: &mut Commands, entity: Entity);
+    fn delete_visual(&self, commands: &mut Commands, index: &mut GraphIndex, node_id: NodeId, entity: Entity);
 
     /// Map domain node update to visual component update
     fn update_visual(&self, commands: &mut Commands, entity: Entity, update: NodeUpdate);
@@ -23,10 +69,10 @@ pub trait NodeMorphism {
 /// Morphism from domain edge operations to visual edge operations
 pub trait EdgeMorphism {
     /// Map domain edge creation to visual line/curve creation
-    fn create_visual(&self, commands: &mut Commands, edge_id: EdgeId, source: Entity, target: Entity) -> Entity;
+    fn create_visual(&self, commands: &mut Commands, index: &mut GraphIndex, edge_id: EdgeId, source: Entity, target: Entity) -> Entity;
 
     /// Map domain edge deletion to visual removal
-    fn delete_visual(&self, commands: &mut Commands, entity: Entity);
+    fn delete_visual(&self, commands: &mut Commands, index: &mut GraphIndex, edge_id: EdgeId, entity: Entity);
 
     /// Map domain edge update to visual update
     fn update_visual(&self, commands: &mut Commands, entity: Entity, update: EdgeUpdate);
@@ -42,6 +88,60 @@ pub trait InteractionMorphism {
 
     /// Map keyboard input to domain command
     fn map_keyboard(&self, key: KeyCode, modifiers: Modifiers) -> Option<DomainCommand>;
+
+    /// Map pointer-enter on a node to a domain hover-start event
+    fn map_hover_enter(&self, entity: Entity) -> NodeHovered;
+
+    /// Map pointer-exit on a node to a domain hover-end event
+    fn map_hover_exit(&self, entity: Entity) -> NodeUnhovered;
+
+    /// Map pointer-enter on an edge to a domain hover-start event
+    fn map_edge_hover_enter(&self, entity: Entity) -> EdgeHovered;
+
+    /// Map pointer-exit on an edge to a domain hover-end event
+    fn map_edge_hover_exit(&self, entity: Entity) -> EdgeUnhovered;
+}
+
+/// System mapping `bevy_mod_picking` pointer events to domain hover events.
+///
+/// Consumes `Pointer<Over>`/`Pointer<Out>` targeting a [`crate::components::NodeVisual`]
+/// or [`crate::components::EdgeVisual`] (any other pickable entity, e.g. a transform-gizmo
+/// arrow, is ignored), inserts/removes the [`crate::components::Hovered`] marker so
+/// downstream rendering can highlight the entity, and forwards the node- or edge-appropriate
+/// domain event so non-visual consumers (e.g. a tree-view panel) stay in sync with the 3D view.
+pub fn handle_pointer_hover<M: InteractionMorphism + Resource>(
+    mut commands: Commands,
+    morphism: Res<M>,
+    mut hover_enter: EventReader<bevy_mod_picking::events::Pointer<bevy_mod_picking::events::Over>>,
+    mut hover_exit: EventReader<bevy_mod_picking::events::Pointer<bevy_mod_picking::events::Out>>,
+    nodes: Query<(), With<crate::components::NodeVisual>>,
+    edges: Query<(), With<crate::components::EdgeVisual>>,
+    mut hovered_events: EventWriter<NodeHovered>,
+    mut unhovered_events: EventWriter<NodeUnhovered>,
+    mut edge_hovered_events: EventWriter<EdgeHovered>,
+    mut edge_unhovered_events: EventWriter<EdgeUnhovered>,
+) {
+    for event in hover_enter.read() {
+        let entity = event.target;
+        if nodes.get(entity).is_ok() {
+            commands.entity(entity).insert(crate::components::Hovered);
+            hovered_events.send(morphism.map_hover_enter(entity));
+        } else if edges.get(entity).is_ok() {
+            commands.entity(entity).insert(crate::components::Hovered);
+            edge_hovered_events.send(morphism.map_edge_hover_enter(entity));
+        }
+    }
+
+    for event in hover_exit.read() {
+        let entity = event.target;
+        if nodes.get(entity).is_ok() {
+            commands.entity(entity).remove::<crate::components::Hovered>();
+            unhovered_events.send(morphism.map_hover_exit(entity));
+        } else if edges.get(entity).is_ok() {
+            commands.entity(entity).remove::<crate::components::Hovered>();
+            edge_unhovered_events.send(morphism.map_edge_hover_exit(entity));
+        }
+    }
 }
 
 /// Composition of morphisms
@@ -99,11 +199,14 @@ impl IsomorphismVerifier {
 pub struct StandardNodeMorphism;
 
 impl NodeMorphism for StandardNodeMorphism {
-    fn create_visual(&self, commands: &mut Commands, node_id: NodeId, graph_id: GraphId, position: Vec3) -> Entity {
-        commands.spawn(crate::components::NodeVisualBundle::new(node_id, graph_id, position)).id()
+    fn create_visual(&self, commands: &mut Commands, index: &mut GraphIndex, node_id: NodeId, graph_id: GraphId, position: Vec3) -> Entity {
+        let entity = commands.spawn(crate::components::NodeVisualBundle::new(node_id, graph_id, position)).id();
+        index.insert_node(node_id, entity);
+        entity
     }
 
-    fn delete_visual(&self, commands: &mut Commands, entity: Entity) {
+    fn delete_visual(&self, commands: &mut Commands, index: &mut GraphIndex, node_id: NodeId, entity: Entity) {
+        index.remove_node(&node_id);
         commands.entity(entity).despawn_recursive();
     }
 
@@ -123,6 +226,7 @@ impl NodeMorphism for StandardNodeMorphism {
     }
 }
 
+
 /// Helper types for morphism parameters
 #[derive(Debug, Clone)]
 pub enum NodeUpdate {
@@ -148,7 +252,318 @@ pub enum DomainCommand {
     CreateNode { position: Vec3 },
     DeleteSelected,
     ConnectSelected,
-    LayoutGraph,
+    LayoutGraph { graph_id: GraphId },
+    Undo,
+    Redo,
+}
+
+/// A graph element id, used to describe what a [`MorphismRecord`] touches or depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GraphElementId {
+    Node(NodeId),
+    Edge(EdgeId),
+}
+
+/// A single reversible domain mutation, as recorded by [`MorphismHistory`].
+///
+/// Each variant pairs the mutation that was applied with enough information to
+/// construct its inverse (spawn↔despawn, old↔new position) and to know which
+/// other graph elements it depends on.
+#[derive(Debug, Clone)]
+pub enum MorphismRecord {
+    CreateNode { node_id: NodeId, graph_id: GraphId, position: Vec3 },
+    DeleteNode { node_id: NodeId, graph_id: GraphId, position: Vec3 },
+    CreateEdge { edge_id: EdgeId, graph_id: GraphId, source: NodeId, target: NodeId },
+    DeleteEdge { edge_id: EdgeId, graph_id: GraphId, source: NodeId, target: NodeId },
+    MoveNode { node_id: NodeId, old_position: Vec3, new_position: Vec3 },
+}
+
+impl MorphismRecord {
+    /// The inverse of this record: applying it undoes the original mutation.
+    pub fn inverse(&self) -> MorphismRecord {
+        match *self {
+            MorphismRecord::CreateNode { node_id, graph_id, position } => {
+                MorphismRecord::DeleteNode { node_id, graph_id, position }
+            }
+            MorphismRecord::DeleteNode { node_id, graph_id, position } => {
+                MorphismRecord::CreateNode { node_id, graph_id, position }
+            }
+            MorphismRecord::CreateEdge { edge_id, graph_id, source, target } => {
+                MorphismRecord::DeleteEdge { edge_id, graph_id, source, target }
+            }
+            MorphismRecord::DeleteEdge { edge_id, graph_id, source, target } => {
+                MorphismRecord::CreateEdge { edge_id, graph_id, source, target }
+            }
+            MorphismRecord::MoveNode { node_id, old_position, new_position } => {
+                MorphismRecord::MoveNode { node_id, old_position: new_position, new_position: old_position }
+            }
+        }
+    }
+
+    /// The graph element this record creates, deletes or moves (not merely reads).
+    pub fn touches(&self) -> GraphElementId {
+        match *self {
+            MorphismRecord::CreateNode { node_id, .. } | MorphismRecord::DeleteNode { node_id, .. } => {
+                GraphElementId::Node(node_id)
+            }
+            MorphismRecord::CreateEdge { edge_id, .. } | MorphismRecord::DeleteEdge { edge_id, .. } => {
+                GraphElementId::Edge(edge_id)
+            }
+            MorphismRecord::MoveNode { node_id, .. } => GraphElementId::Node(node_id),
+        }
+    }
+
+    /// Other graph elements that must already exist for this record's inverse to apply.
+    ///
+    /// An edge create/delete depends on both of its endpoint nodes; creating or moving
+    /// a node depends on nothing else.
+    pub fn depends_on(&self) -> Vec<GraphElementId> {
+        match *self {
+            MorphismRecord::CreateEdge { source, target, .. }
+            | MorphismRecord::DeleteEdge { source, target, .. } => {
+                vec![GraphElementId::Node(source), GraphElementId::Node(target)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Outcome of attempting to undo or redo the most recent [`MorphismRecord`].
+#[derive(Debug, Clone)]
+pub enum HistoryOutcome {
+    /// The record (and any records cascaded to satisfy its dependencies) were applied,
+    /// in the order they should be replayed.
+    Applied(Vec<MorphismRecord>),
+    /// There was nothing to undo/redo.
+    Empty,
+    /// The record depends on an element that no longer exists and has no matching
+    /// record available to cascade, so the undo/redo was rejected.
+    Rejected(MorphismRecord),
+}
+
+/// Records applied domain mutations as reversible [`MorphismRecord`]s and walks the
+/// undo/redo stacks, cascading through dependent records like a change-graph.
+#[derive(Resource, Default)]
+pub struct MorphismHistory {
+    applied: Vec<MorphismRecord>,
+    /// Undone cascades, most recent last. Each entry is the full group of records a
+    /// single [`undo`](MorphismHistory::undo) call popped off `applied` (the primary
+    /// record plus any dependents cascaded in to satisfy it), kept together so
+    /// [`redo`](MorphismHistory::redo) reapplies the whole cascade rather than a fragment.
+    undone: Vec<Vec<MorphismRecord>>,
+    /// Elements whose next `Create`/`Delete`/`Move` event was produced by
+    /// [`apply_undo_redo`] replaying an inverse record rather than a fresh mutation.
+    /// [`record_morphism_history`] consults this before recording, so an undo doesn't
+    /// get pushed right back onto `applied` (which would clear the redo stack and
+    /// corrupt the history). Keyed by element and counted rather than a single flag,
+    /// since a cascaded undo can replay several elements in the same frame.
+    replaying: HashMap<GraphElementId, u32>,
+}
+
+impl MorphismHistory {
+    /// Record a freshly applied mutation, clearing the redo stack.
+    pub fn record(&mut self, record: MorphismRecord) {
+        self.applied.push(record);
+        self.undone.clear();
+    }
+
+    /// Mark that the next observation of `element` by [`record_morphism_history`] was
+    /// produced by a replayed undo/redo, not a fresh mutation.
+    fn mark_replaying(&mut self, element: GraphElementId) {
+        *self.replaying.entry(element).or_insert(0) += 1;
+    }
+
+    /// Consume one pending replay marker for `element`, if present. Returns `true` if
+    /// the corresponding event should be skipped rather than recorded.
+    fn take_replaying(&mut self, element: GraphElementId) -> bool {
+        match self.replaying.get_mut(&element) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                if *count == 0 {
+                    self.replaying.remove(&element);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Undo the most recently applied record, cascading to satisfy dependencies.
+    ///
+    /// Before unrolling a record, verifies that every element it depends on either
+    /// still exists in `index` or has its own still-applied record to cascade through
+    /// first (e.g. undoing an edge deletion whose endpoint node was since deleted).
+    pub fn undo(&mut self, index: &GraphIndex) -> HistoryOutcome {
+        let Some(record) = self.applied.pop() else {
+            return HistoryOutcome::Empty;
+        };
+
+        let mut cascade = Vec::new();
+        if !self.satisfy_dependencies(&record, index, &mut cascade) {
+            self.applied.push(record.clone());
+            return HistoryOutcome::Rejected(record);
+        }
+
+        cascade.push(record.clone());
+        let inverses = cascade.iter().map(MorphismRecord::inverse).collect();
+        self.undone.push(cascade);
+        HistoryOutcome::Applied(inverses)
+    }
+
+    /// Redo the most recently undone cascade, reapplying every record it contains
+    /// (unchanged, not inverted) in the same order they were originally applied.
+    pub fn redo(&mut self) -> HistoryOutcome {
+        match self.undone.pop() {
+            Some(cascade) => {
+                self.applied.extend(cascade.iter().cloned());
+                HistoryOutcome::Applied(cascade)
+            }
+            None => HistoryOutcome::Empty,
+        }
+    }
+
+    /// Ensure every dependency of `record` is satisfiable, recursively cascading
+    /// through still-applied records and appending them (in undo order) to `cascade`.
+    fn satisfy_dependencies(
+        &mut self,
+        record: &MorphismRecord,
+        index: &GraphIndex,
+        cascade: &mut Vec<MorphismRecord>,
+    ) -> bool {
+        for dependency in record.depends_on() {
+            let exists = match dependency {
+                GraphElementId::Node(node_id) => index.node_entity(&node_id).is_some(),
+                GraphElementId::Edge(edge_id) => index.edge_entity(&edge_id).is_some(),
+            };
+            if exists {
+                continue;
+            }
+
+            let Some(position) = self.applied.iter().rposition(|r| r.touches() == dependency) else {
+                return false;
+            };
+            let dependent = self.applied.remove(position);
+            if !self.satisfy_dependencies(&dependent, index, cascade) {
+                self.applied.insert(position, dependent);
+                return false;
+            }
+            cascade.push(dependent);
+        }
+        true
+    }
+}
+
+/// One of the three constrained drag axes offered by the transform gizmo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl GizmoAxis {
+    /// Unit direction of this axis in world space.
+    pub fn direction(&self) -> Vec3 {
+        match self {
+            GizmoAxis::X => Vec3::X,
+            GizmoAxis::Y => Vec3::Y,
+            GizmoAxis::Z => Vec3::Z,
+        }
+    }
+}
+
+/// Morphism from a selected node to its transform-gizmo visuals, and from a
+/// constrained drag on one of the gizmo's axis arrows to a domain position update.
+pub trait GizmoMorphism {
+    /// Spawn the X/Y/Z axis-arrow children for a newly selected node, returning
+    /// their entities in `[X, Y, Z]` order.
+    fn spawn_arrows(&self, commands: &mut Commands, node_entity: Entity) -> [Entity; 3];
+
+    /// Despawn a node's axis-arrow children on deselection.
+    fn despawn_arrows(&self, commands: &mut Commands, arrows: [Entity; 3]);
+
+    /// Project a free-move drag delta onto a single axis.
+    fn constrain_drag(&self, axis: GizmoAxis, delta: Vec3) -> Vec3 {
+        let direction = axis.direction();
+        direction * delta.dot(direction)
+    }
+}
+
+/// Default gizmo morphism: colored unit-arrow meshes along each axis.
+#[derive(Default)]
+pub struct StandardGizmoMorphism;
+
+impl GizmoMorphism for StandardGizmoMorphism {
+    fn spawn_arrows(&self, commands: &mut Commands, node_entity: Entity) -> [Entity; 3] {
+        let axes = [
+            (GizmoAxis::X, Color::srgb(1.0, 0.0, 0.0)),
+            (GizmoAxis::Y, Color::srgb(0.0, 1.0, 0.0)),
+            (GizmoAxis::Z, Color::srgb(0.0, 0.0, 1.0)),
+        ];
+
+        axes.map(|(axis, color)| {
+            let arrow = commands
+                .spawn(crate::components::GizmoArrowBundle::new(axis, color))
+                .id();
+            commands.entity(node_entity).add_child(arrow);
+            arrow
+        })
+    }
+
+    fn despawn_arrows(&self, commands: &mut Commands, arrows: [Entity; 3]) {
+        for arrow in arrows {
+            commands.entity(arrow).despawn_recursive();
+        }
+    }
+}
+
+/// System that spawns the transform gizmo's axis arrows when a node becomes `Selected`.
+pub fn spawn_gizmo_on_selection(
+    mut commands: Commands,
+    morphism: Local<StandardGizmoMorphism>,
+    newly_selected: Query<Entity, Added<crate::components::Selected>>,
+) {
+    for node_entity in newly_selected.iter() {
+        let arrows = morphism.spawn_arrows(&mut commands, node_entity);
+        commands
+            .entity(node_entity)
+            .insert(crate::components::GizmoArrows(arrows));
+    }
+}
+
+/// System that despawns the transform gizmo's axis arrows when a node is deselected.
+pub fn despawn_gizmo_on_deselection(
+    mut commands: Commands,
+    morphism: Local<StandardGizmoMorphism>,
+    mut removed: RemovedComponents<crate::components::Selected>,
+    gizmos: Query<&crate::components::GizmoArrows>,
+) {
+    for node_entity in removed.read() {
+        if let Ok(crate::components::GizmoArrows(arrows)) = gizmos.get(node_entity) {
+            morphism.despawn_arrows(&mut commands, *arrows);
+            commands.entity(node_entity).remove::<crate::components::GizmoArrows>();
+        }
+    }
+}
+
+/// System that constrains a drag to the active gizmo arrow's axis, or leaves it
+/// free-move when the node body itself (rather than an arrow) is being dragged.
+pub fn constrain_gizmo_drag(
+    morphism: Local<StandardGizmoMorphism>,
+    arrows: Query<&crate::components::GizmoArrow>,
+    mut drags: EventReader<crate::events::GizmoDragged>,
+    mut position_changed: EventWriter<NodePositionChanged>,
+) {
+    for drag in drags.read() {
+        let delta = match arrows.get(drag.dragged_entity) {
+            Ok(arrow) => morphism.constrain_drag(arrow.axis, drag.delta),
+            Err(_) => drag.delta,
+        };
+        position_changed.send(NodePositionChanged {
+            node_id: drag.node_id,
+            new_position: drag.origin + delta,
+        });
+    }
 }
 
 /// System functions for morphism operations
@@ -156,28 +571,28 @@ pub enum DomainCommand {
 /// System to create node visuals from events
 pub fn create_node_visual(
     mut commands: Commands,
+    mut index: ResMut<GraphIndex>,
     mut events: EventReader<CreateNodeVisual>,
 ) {
     for event in events.read() {
-        commands.spawn(crate::components::NodeVisualBundle::new(
+        let entity = commands.spawn(crate::components::NodeVisualBundle::new(
             event.node_id,
             event.graph_id,
             event.position,
-        ));
+        )).id();
+        index.insert_node(event.node_id, entity);
     }
 }
 
 /// System to remove node visuals from events
 pub fn remove_node_visual(
     mut commands: Commands,
+    mut index: ResMut<GraphIndex>,
     mut events: EventReader<RemoveNodeVisual>,
-    query: Query<(Entity, &crate::components::NodeVisual)>,
 ) {
     for event in events.read() {
-        for (entity, node_visual) in query.iter() {
-            if node_visual.node_id == event.node_id {
-                commands.entity(entity).despawn();
-            }
+        if let Some(entity) = index.remove_node(&event.node_id) {
+            commands.entity(entity).despawn();
         }
     }
 }
@@ -185,30 +600,21 @@ pub fn remove_node_visual(
 /// System to create edge visuals from events
 pub fn create_edge_visual(
     mut commands: Commands,
+    mut index: ResMut<GraphIndex>,
     mut events: EventReader<CreateEdgeVisual>,
-    nodes: Query<(Entity, &crate::components::NodeVisual)>,
 ) {
     for event in events.read() {
-        // Find source and target entities
-        let mut source_entity = None;
-        let mut target_entity = None;
-
-        for (entity, node_visual) in nodes.iter() {
-            if node_visual.node_id == event.source_id {
-                source_entity = Some(entity);
-            }
-            if node_visual.node_id == event.target_id {
-                target_entity = Some(entity);
-            }
-        }
+        let source_entity = index.node_entity(&event.source_id);
+        let target_entity = index.node_entity(&event.target_id);
 
         if let (Some(source), Some(target)) = (source_entity, target_entity) {
-            commands.spawn(crate::components::EdgeVisualBundle::new(
+            let entity = commands.spawn(crate::components::EdgeVisualBundle::new(
                 event.edge_id,
                 event.graph_id,
                 source,
                 target,
-            ));
+            )).id();
+            index.insert_edge(event.edge_id, entity);
         }
     }
 }
@@ -216,26 +622,25 @@ pub fn create_edge_visual(
 /// System to remove edge visuals from events
 pub fn remove_edge_visual(
     mut commands: Commands,
+    mut index: ResMut<GraphIndex>,
     mut events: EventReader<RemoveEdgeVisual>,
-    query: Query<(Entity, &crate::components::EdgeVisual)>,
 ) {
     for event in events.read() {
-        for (entity, edge_visual) in query.iter() {
-            if edge_visual.edge_id == event.edge_id {
-                commands.entity(entity).despawn();
-            }
+        if let Some(entity) = index.remove_edge(&event.edge_id) {
+            commands.entity(entity).despawn();
         }
     }
 }
 
 /// System to update node positions
 pub fn update_node_position(
+    index: Res<GraphIndex>,
     mut events: EventReader<NodePositionChanged>,
-    mut query: Query<(&crate::components::NodeVisual, &mut Transform)>,
+    mut query: Query<&mut Transform, With<crate::components::NodeVisual>>,
 ) {
     for event in events.read() {
-        for (node_visual, mut transform) in query.iter_mut() {
-            if node_visual.node_id == event.node_id {
+        if let Some(entity) = index.node_entity(&event.node_id) {
+            if let Ok(mut transform) = query.get_mut(entity) {
                 transform.translation = event.new_position;
             }
         }
@@ -244,12 +649,13 @@ pub fn update_node_position(
 
 /// System to update node metadata
 pub fn update_node_metadata(
+    index: Res<GraphIndex>,
     mut events: EventReader<NodeMetadataChanged>,
     mut query: Query<&mut crate::components::NodeVisual>,
 ) {
     for event in events.read() {
-        for mut node_visual in query.iter_mut() {
-            if node_visual.node_id == event.node_id {
+        if let Some(entity) = index.node_entity(&event.node_id) {
+            if let Ok(mut _node_visual) = query.get_mut(entity) {
                 // Update metadata (this would be expanded based on actual metadata structure)
                 // For now, we just acknowledge the event
             }
@@ -259,15 +665,939 @@ pub fn update_node_metadata(
 
 /// System to update edge metadata
 pub fn update_edge_metadata(
+    index: Res<GraphIndex>,
     mut events: EventReader<EdgeMetadataChanged>,
     mut query: Query<&mut crate::components::EdgeVisual>,
 ) {
     for event in events.read() {
-        for mut edge_visual in query.iter_mut() {
-            if edge_visual.edge_id == event.edge_id {
+        if let Some(entity) = index.edge_entity(&event.edge_id) {
+            if let Ok(mut _edge_visual) = query.get_mut(entity) {
                 // Update metadata (this would be expanded based on actual metadata structure)
                 // For now, we just acknowledge the event
             }
         }
     }
 }
+
+/// System recording every applied node/edge mutation into [`MorphismHistory`] so it
+/// can later be undone.
+///
+/// Must run before [`update_node_position`] so that, for a `NodePositionChanged` event,
+/// the queried `Transform` still reflects the pre-update position and can be captured as
+/// the move's inverse, and before [`remove_node_visual`], which drops the node from
+/// [`GraphIndex`] that a `DeleteNode` record reads its position through. Must also run after
+/// [`apply_undo_redo`], whose replayed events carry a marker in [`MorphismHistory`] that
+/// this system consumes instead of re-recording them as fresh mutations (which would
+/// otherwise clobber the redo stack on every undo).
+pub fn record_morphism_history(
+    mut history: ResMut<MorphismHistory>,
+    mut node_created: EventReader<CreateNodeVisual>,
+    mut node_removed: EventReader<RemoveNodeVisual>,
+    mut edge_created: EventReader<CreateEdgeVisual>,
+    mut edge_removed: EventReader<RemoveEdgeVisual>,
+    mut node_moved: EventReader<NodePositionChanged>,
+    nodes: Query<&Transform, With<crate::components::NodeVisual>>,
+    index: Res<GraphIndex>,
+) {
+    for event in node_created.read() {
+        if history.take_replaying(GraphElementId::Node(event.node_id)) {
+            continue;
+        }
+        history.record(MorphismRecord::CreateNode {
+            node_id: event.node_id,
+            graph_id: event.graph_id,
+            position: event.position,
+        });
+    }
+
+    for event in node_removed.read() {
+        if history.take_replaying(GraphElementId::Node(event.node_id)) {
+            continue;
+        }
+        if let Some(position) = index
+            .node_entity(&event.node_id)
+            .and_then(|entity| nodes.get(entity).ok())
+            .map(|transform| transform.translation)
+        {
+            history.record(MorphismRecord::DeleteNode {
+                node_id: event.node_id,
+                graph_id: event.graph_id,
+                position,
+            });
+        }
+    }
+
+    for event in edge_created.read() {
+        if history.take_replaying(GraphElementId::Edge(event.edge_id)) {
+            continue;
+        }
+        history.record(MorphismRecord::CreateEdge {
+            edge_id: event.edge_id,
+            graph_id: event.graph_id,
+            source: event.source_id,
+            target: event.target_id,
+        });
+    }
+
+    for event in edge_removed.read() {
+        if history.take_replaying(GraphElementId::Edge(event.edge_id)) {
+            continue;
+        }
+        history.record(MorphismRecord::DeleteEdge {
+            edge_id: event.edge_id,
+            graph_id: event.graph_id,
+            source: event.source_id,
+            target: event.target_id,
+        });
+    }
+
+    for event in node_moved.read() {
+        if history.take_replaying(GraphElementId::Node(event.node_id)) {
+            continue;
+        }
+        if let Some(old_position) = index
+            .node_entity(&event.node_id)
+            .and_then(|entity| nodes.get(entity).ok())
+            .map(|transform| transform.translation)
+        {
+            history.record(MorphismRecord::MoveNode {
+                node_id: event.node_id,
+                old_position,
+                new_position: event.new_position,
+            });
+        }
+    }
+}
+
+/// System handling [`DomainCommand::Undo`]/[`DomainCommand::Redo`], replaying the
+/// resulting inverse records as the same visual/domain events the forward mutations
+/// would have produced.
+///
+/// Before sending each replayed event, marks its element as replaying in
+/// [`MorphismHistory`] so [`record_morphism_history`] (which must run after this system)
+/// skips it instead of recording it as a brand-new mutation.
+pub fn apply_undo_redo(
+    mut commands: EventReader<DomainCommand>,
+    mut history: ResMut<MorphismHistory>,
+    index: Res<GraphIndex>,
+    mut node_created: EventWriter<CreateNodeVisual>,
+    mut node_removed: EventWriter<RemoveNodeVisual>,
+    mut edge_created: EventWriter<CreateEdgeVisual>,
+    mut edge_removed: EventWriter<RemoveEdgeVisual>,
+    mut node_moved: EventWriter<NodePositionChanged>,
+) {
+    for command in commands.read() {
+        let outcome = match command {
+            DomainCommand::Undo => history.undo(&index),
+            DomainCommand::Redo => history.redo(),
+            _ => continue,
+        };
+
+        let records = match outcome {
+            HistoryOutcome::Applied(records) => records,
+            HistoryOutcome::Empty | HistoryOutcome::Rejected(_) => continue,
+        };
+
+        for record in records {
+            history.mark_replaying(record.touches());
+            match record {
+                MorphismRecord::CreateNode { node_id, graph_id, position } => {
+                    node_created.send(CreateNodeVisual { node_id, graph_id, position });
+                }
+                MorphismRecord::DeleteNode { node_id, .. } => {
+                    node_removed.send(RemoveNodeVisual { node_id });
+                }
+                MorphismRecord::CreateEdge { edge_id, graph_id, source, target } => {
+                    edge_created.send(CreateEdgeVisual { edge_id, graph_id, source_id: source, target_id: target });
+                }
+                MorphismRecord::DeleteEdge { edge_id, .. } => {
+                    edge_removed.send(RemoveEdgeVisual { edge_id });
+                }
+                MorphismRecord::MoveNode { node_id, new_position, .. } => {
+                    node_moved.send(NodePositionChanged { node_id, new_position });
+                }
+            }
+        }
+    }
+}
+
+/// Tuning parameters for [`layout_graph`]'s Fruchterman-Reingold force-directed layout.
+#[derive(Resource, Clone, Copy)]
+pub struct LayoutConfig {
+    /// Layout area (`C · sqrt(area / |nodes|)` gives the ideal inter-node distance `k`).
+    pub area: f32,
+    /// Scaling constant `C` in the ideal-distance formula.
+    pub constant: f32,
+    /// Number of relaxation passes to run per `LayoutGraph` command.
+    pub iterations: u32,
+    /// Displacement cap ("temperature") at the first iteration. [`relax_once`] cools it
+    /// linearly to 0 over `iterations` passes, so early passes can make large corrections
+    /// while late passes only fine-tune, preventing oscillation as the layout converges.
+    pub max_displacement: f32,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            area: 1_000.0,
+            constant: 1.0,
+            iterations: 50,
+            max_displacement: 10.0,
+        }
+    }
+}
+
+/// System implementing [`DomainCommand::LayoutGraph`] as Fruchterman-Reingold
+/// force-directed layout: nodes repel each other with magnitude `k² / d`, edges pull
+/// their endpoints together with magnitude `d² / k`, and each node moves by its summed
+/// displacement for the graph, capped by a linearly-cooling temperature (see
+/// [`LayoutConfig::max_displacement`]).
+///
+/// Relaxation runs over a local snapshot of positions rather than mutating `Transform`
+/// directly; once all passes finish, the final positions are emitted as
+/// [`NodePositionChanged`] events, so [`update_node_position`] applies the result,
+/// [`record_morphism_history`] can undo it, and [`route_edges`] recomputes affected routes.
+pub fn layout_graph(
+    mut commands: EventReader<DomainCommand>,
+    config: Res<LayoutConfig>,
+    nodes: Query<(&crate::components::NodeVisual, &Transform)>,
+    edges: Query<&crate::components::EdgeVisual>,
+    mut position_changed: EventWriter<NodePositionChanged>,
+) {
+    for command in commands.read() {
+        let DomainCommand::LayoutGraph { graph_id } = command else {
+            continue;
+        };
+
+        let mut positions: HashMap<NodeId, Vec3> = nodes
+            .iter()
+            .filter(|(node_visual, _)| node_visual.graph_id == *graph_id)
+            .map(|(node_visual, transform)| (node_visual.node_id, transform.translation))
+            .collect();
+
+        if positions.len() < 2 {
+            continue;
+        }
+
+        for iteration in 0..config.iterations {
+            relax_once(*graph_id, &config, iteration, &mut positions, &edges);
+        }
+
+        for (node_id, new_position) in positions {
+            position_changed.send(NodePositionChanged { node_id, new_position });
+        }
+    }
+}
+
+/// A small deterministic jitter direction for two coincident nodes, derived from their
+/// ids so that relaxing the same degenerate overlap always nudges it the same way
+/// instead of leaving the repulsion direction (and thus the position) undefined.
+fn jitter_direction(id_a: NodeId, id_b: NodeId) -> Vec3 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    id_a.hash(&mut hasher);
+    id_b.hash(&mut hasher);
+    let angle = (hasher.finish() as f32 / u64::MAX as f32) * std::f32::consts::TAU;
+    Vec3::new(angle.cos(), angle.sin(), 0.0)
+}
+
+/// The unit direction from `b` to `a`, falling back to a deterministic jitter when the
+/// two positions coincide (`delta.normalize()` would otherwise yield `NaN`).
+fn repulsion_direction(delta: Vec3, id_a: NodeId, id_b: NodeId) -> Vec3 {
+    let direction = delta.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        jitter_direction(id_a, id_b)
+    } else {
+        direction
+    }
+}
+
+/// One Fruchterman-Reingold relaxation pass over the nodes/edges belonging to `graph_id`,
+/// working against a local `positions` snapshot rather than the ECS `Transform`s.
+/// `iteration` (0-indexed, out of `config.iterations`) linearly cools the displacement
+/// cap from `config.max_displacement` toward 0 (see [`cooled_temperature`]).
+fn relax_once(
+    graph_id: GraphId,
+    config: &LayoutConfig,
+    iteration: u32,
+    positions: &mut HashMap<NodeId, Vec3>,
+    edges: &Query<&crate::components::EdgeVisual>,
+) {
+    let snapshot: Vec<(NodeId, Vec3)> = positions.iter().map(|(id, pos)| (*id, *pos)).collect();
+    let k = config.constant * (config.area / snapshot.len() as f32).sqrt();
+    let mut displacements: HashMap<NodeId, Vec3> = snapshot.iter().map(|(id, _)| (*id, Vec3::ZERO)).collect();
+
+    for &(id_a, pos_a) in &snapshot {
+        for &(id_b, pos_b) in &snapshot {
+            if id_a == id_b {
+                continue;
+            }
+            let delta = pos_a - pos_b;
+            let distance = delta.length().max(0.01);
+            let repulsion = repulsion_direction(delta, id_a, id_b) * (k * k / distance);
+            *displacements.get_mut(&id_a).unwrap() += repulsion;
+        }
+    }
+
+    for edge in edges.iter().filter(|edge| edge.graph_id == graph_id) {
+        let source = snapshot.iter().find(|(id, _)| *id == edge.source_id).map(|(_, p)| *p);
+        let target = snapshot.iter().find(|(id, _)| *id == edge.target_id).map(|(_, p)| *p);
+        let (Some(source_pos), Some(target_pos)) = (source, target) else {
+            continue;
+        };
+
+        let delta = source_pos - target_pos;
+        let distance = delta.length().max(0.01);
+        let attraction = repulsion_direction(delta, edge.source_id, edge.target_id) * (distance * distance / k);
+
+        if let Some(disp) = displacements.get_mut(&edge.source_id) {
+            *disp -= attraction;
+        }
+        if let Some(disp) = displacements.get_mut(&edge.target_id) {
+            *disp += attraction;
+        }
+    }
+
+    let temperature = cooled_temperature(config, iteration);
+    for (node_id, position) in positions.iter_mut() {
+        if let Some(displacement) = displacements.get(node_id) {
+            *position += displacement.clamp_length_max(temperature);
+        }
+    }
+}
+
+/// The displacement cap for `iteration` (0-indexed, out of `config.iterations`):
+/// `config.max_displacement` cooled linearly toward 0, so the final pass
+/// (`iteration == config.iterations - 1`) is capped at `max_displacement / iterations`.
+fn cooled_temperature(config: &LayoutConfig, iteration: u32) -> f32 {
+    config.max_displacement * (1.0 - iteration as f32 / config.iterations as f32)
+}
+
+/// How an edge's visual path is computed from its endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeRoutingMode {
+    /// A straight line from source to target (the original behavior).
+    Straight,
+    /// A polyline computed by [`route_edges`] that avoids other nodes' bounding volumes.
+    Routed,
+}
+
+/// Tuning parameters for [`route_edges`]'s A* pathfinder.
+#[derive(Resource, Clone, Copy)]
+pub struct EdgeRoutingConfig {
+    pub mode: EdgeRoutingMode,
+    /// Side length of one A* grid cell.
+    pub cell_size: f32,
+    /// Radius around a node's center treated as blocked.
+    pub node_radius: f32,
+    /// Extra cost added whenever the path changes direction, biasing toward straighter routes.
+    pub turn_penalty: f32,
+}
+
+impl Default for EdgeRoutingConfig {
+    fn default() -> Self {
+        EdgeRoutingConfig {
+            mode: EdgeRoutingMode::Straight,
+            cell_size: 1.0,
+            node_radius: 0.5,
+            turn_penalty: 0.5,
+        }
+    }
+}
+
+/// A cell in the coarse A* grid spanning an edge's bounding box, in the XY plane at a
+/// fixed height (edges are routed as if flattened onto the plane their endpoints sit near).
+type GridCell = (i32, i32);
+
+/// System that (re-)computes a routed polyline for every edge whose source/target moved
+/// or that was just created, when [`EdgeRoutingConfig::mode`] is [`EdgeRoutingMode::Routed`].
+///
+/// Runs A* over a coarse grid from source to target; the search is not confined to the
+/// endpoints' bounding box, but stops after a fixed number of cell expansions. Other
+/// nodes' bounding volumes are blocked cells, and path cost is Euclidean distance plus
+/// a turn penalty. The resulting waypoints are sent as [`crate::events::EdgeRouteChanged`],
+/// which [`apply_edge_route`] turns into the edge's [`crate::components::EdgeRoute`]
+/// control points — an event/component pair rather than an `EdgeUpdate` variant, since
+/// routing needs to resolve both endpoints' current positions by `NodeId` rather than
+/// act on a single already-resolved `Entity` the way [`EdgeMorphism::update_visual`] does.
+pub fn route_edges(
+    config: Res<EdgeRoutingConfig>,
+    index: Res<GraphIndex>,
+    mut edge_created: EventReader<CreateEdgeVisual>,
+    mut node_moved: EventReader<NodePositionChanged>,
+    nodes: Query<(&crate::components::NodeVisual, &Transform)>,
+    edges: Query<&crate::components::EdgeVisual>,
+    mut routed: EventWriter<crate::events::EdgeRouteChanged>,
+) {
+    if config.mode != EdgeRoutingMode::Routed {
+        edge_created.clear();
+        node_moved.clear();
+        return;
+    }
+
+    let obstacles: Vec<(NodeId, Vec3)> = nodes.iter().map(|(nv, t)| (nv.node_id, t.translation)).collect();
+    let position_of = |node_id: NodeId| obstacles.iter().find(|(id, _)| *id == node_id).map(|(_, p)| *p);
+
+    let mut to_route: Vec<EdgeId> = edge_created.read().map(|event| event.edge_id).collect();
+
+    for event in node_moved.read() {
+        for edge in edges.iter().filter(|e| e.source_id == event.node_id || e.target_id == event.node_id) {
+            if !to_route.contains(&edge.edge_id) {
+                to_route.push(edge.edge_id);
+            }
+        }
+    }
+
+    for edge in edges.iter().filter(|e| to_route.contains(&e.edge_id)) {
+        let (Some(source), Some(target)) = (position_of(edge.source_id), position_of(edge.target_id)) else {
+            continue;
+        };
+        let blockers: Vec<Vec3> = obstacles
+            .iter()
+            .filter(|(id, _)| *id != edge.source_id && *id != edge.target_id)
+            .map(|(_, pos)| *pos)
+            .collect();
+
+        let waypoints = find_route(source, target, &blockers, &config);
+        routed.send(crate::events::EdgeRouteChanged { edge_id: edge.edge_id, waypoints });
+    }
+
+    let _ = index;
+}
+
+/// System applying routed waypoints computed by [`route_edges`] to the edge's visual.
+pub fn apply_edge_route(
+    mut commands: Commands,
+    index: Res<GraphIndex>,
+    mut events: EventReader<crate::events::EdgeRouteChanged>,
+) {
+    for event in events.read() {
+        if let Some(entity) = index.edge_entity(&event.edge_id) {
+            commands.entity(entity).insert(crate::components::EdgeRoute(event.waypoints.clone()));
+        }
+    }
+}
+
+/// A* pathfinding over a coarse grid in the XY plane, from `start` to `end`, treating
+/// every cell within `ceil(config.node_radius / cell_size)` cells (on both axes, a square)
+/// of each entry in `blockers` as impassable. Cost is
+/// Euclidean distance between cell centers plus `config.turn_penalty` whenever the path
+/// changes direction. Falls back to the direct `[start, end]` line if no path is found.
+fn find_route(start: Vec3, end: Vec3, blockers: &[Vec3], config: &EdgeRoutingConfig) -> Vec<Vec3> {
+    let cell_size = config.cell_size.max(0.01);
+    let z = (start.z + end.z) * 0.5;
+    let to_cell = |p: Vec3| -> GridCell { ((p.x / cell_size).round() as i32, (p.y / cell_size).round() as i32) };
+    let to_world = |c: GridCell| -> Vec3 { Vec3::new(c.0 as f32 * cell_size, c.1 as f32 * cell_size, z) };
+
+    let start_cell = to_cell(start);
+    let end_cell = to_cell(end);
+    let blocked_radius_cells = (config.node_radius / cell_size).ceil() as i32;
+
+    let is_blocked = |cell: GridCell| {
+        if cell == start_cell || cell == end_cell {
+            return false;
+        }
+        blockers.iter().any(|blocker| {
+            let blocker_cell = to_cell(*blocker);
+            (cell.0 - blocker_cell.0).abs() <= blocked_radius_cells && (cell.1 - blocker_cell.1).abs() <= blocked_radius_cells
+        })
+    };
+
+    // A* state is `(cell, dir arrived from)` rather than just `cell`: since turn cost
+    // depends on the incoming direction, the cheapest arrival at a cell from one direction
+    // may not dominate a costlier arrival from another direction that then turns less.
+    // Keying only on `cell` would prune that second arrival even when it yields a cheaper
+    // turn-penalized continuation.
+    type GridState = (GridCell, Option<GridCell>);
+
+    #[derive(PartialEq)]
+    struct Frontier {
+        cost: f32,
+        state: GridState,
+    }
+    impl Eq for Frontier {}
+    impl Ord for Frontier {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            other.cost.partial_cmp(&self.cost).unwrap_or(std::cmp::Ordering::Equal)
+        }
+    }
+    impl PartialOrd for Frontier {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    let heuristic = |cell: GridCell| {
+        let world = to_world(cell);
+        (world - end).length()
+    };
+
+    let mut open = std::collections::BinaryHeap::new();
+    let mut best_cost: HashMap<GridState, f32> = HashMap::new();
+    let mut came_from: HashMap<GridState, GridState> = HashMap::new();
+
+    let start_state: GridState = (start_cell, None);
+    best_cost.insert(start_state, 0.0);
+    open.push(Frontier { cost: heuristic(start_cell), state: start_state });
+
+    const DIRECTIONS: [GridCell; 8] = [
+        (1, 0), (-1, 0), (0, 1), (0, -1),
+        (1, 1), (1, -1), (-1, 1), (-1, -1),
+    ];
+    const MAX_EXPANSIONS: usize = 4_000;
+    let mut expansions = 0;
+    let mut end_state: Option<GridState> = None;
+
+    while let Some(Frontier { state, .. }) = open.pop() {
+        let (cell, arrived_from) = state;
+        if cell == end_cell {
+            end_state = Some(state);
+            break;
+        }
+        expansions += 1;
+        if expansions > MAX_EXPANSIONS {
+            break;
+        }
+
+        let current_cost = *best_cost.get(&state).unwrap_or(&f32::INFINITY);
+
+        for &dir in &DIRECTIONS {
+            let neighbor = (cell.0 + dir.0, cell.1 + dir.1);
+            if is_blocked(neighbor) {
+                continue;
+            }
+            let step_cost = (to_world(neighbor) - to_world(cell)).length();
+            let turn_cost = if arrived_from.is_some_and(|prev| prev != dir) { config.turn_penalty } else { 0.0 };
+            let tentative_cost = current_cost + step_cost + turn_cost;
+
+            let neighbor_state: GridState = (neighbor, Some(dir));
+            if tentative_cost < *best_cost.get(&neighbor_state).unwrap_or(&f32::INFINITY) {
+                best_cost.insert(neighbor_state, tentative_cost);
+                came_from.insert(neighbor_state, state);
+                open.push(Frontier { cost: tentative_cost + heuristic(neighbor), state: neighbor_state });
+            }
+        }
+    }
+
+    let Some(end_state) = end_state else {
+        return vec![start, end];
+    };
+
+    let mut path_cells = vec![end_state.0];
+    let mut cursor = end_state;
+    while let Some(&from) = came_from.get(&cursor) {
+        path_cells.push(from.0);
+        cursor = from;
+    }
+    path_cells.reverse();
+
+    let mut waypoints: Vec<Vec3> = Vec::with_capacity(path_cells.len());
+    waypoints.push(start);
+    for cell in &path_cells[1..path_cells.len().saturating_sub(1)] {
+        waypoints.push(to_world(*cell));
+    }
+    waypoints.push(end);
+    waypoints
+}
+
+/// The graph's structure projected as a tree: roots (nodes with no incoming edge) and
+/// each node's children (nodes reachable via one outgoing edge), kept in sync with the
+/// 3D view by [`sync_tree_view`] rather than recomputed from scratch every frame.
+#[derive(Resource, Default)]
+pub struct TreeView {
+    roots: Vec<NodeId>,
+    children: HashMap<NodeId, Vec<NodeId>>,
+    labels: HashMap<NodeId, String>,
+}
+
+impl TreeView {
+    fn remove_node(&mut self, node_id: &NodeId) {
+        self.roots.retain(|id| id != node_id);
+        self.labels.remove(node_id);
+
+        let orphaned = self.children.remove(node_id).unwrap_or_default();
+        for children in self.children.values_mut() {
+            children.retain(|id| id != node_id);
+        }
+
+        // A removed node's children that have no other parent left would otherwise
+        // silently vanish from the tree; surface them as roots instead.
+        for child in orphaned {
+            let still_referenced = self.children.values().any(|children| children.contains(&child));
+            if !still_referenced && !self.roots.contains(&child) {
+                self.roots.push(child);
+            }
+        }
+    }
+}
+
+/// A single row of a flattened, cycle-safe view of a [`TreeView`], ready for an egui
+/// side panel to render as an indented, collapsible list.
+#[derive(Debug, Clone)]
+pub struct TreeRow {
+    pub node_id: NodeId,
+    pub depth: usize,
+    pub label: String,
+    pub has_children: bool,
+    /// True if this row re-visits a node already shown higher in the same branch; its
+    /// children are not expanded further.
+    pub is_cycle: bool,
+}
+
+/// Morphism from the graph's node/edge structure to a collapsible tree-view projection.
+pub trait TreeMorphism {
+    /// Flatten `view` into rows in depth-first order, marking already-visited nodes
+    /// within the current branch as cycles instead of recursing into them again.
+    fn project(&self, view: &TreeView) -> Vec<TreeRow> {
+        let mut rows = Vec::new();
+        for &root in &view.roots {
+            let mut visiting = Vec::new();
+            self.visit(view, root, 0, &mut visiting, &mut rows);
+        }
+        rows
+    }
+
+    fn visit(&self, view: &TreeView, node_id: NodeId, depth: usize, visiting: &mut Vec<NodeId>, rows: &mut Vec<TreeRow>) {
+        let label = view.labels.get(&node_id).cloned().unwrap_or_else(|| format!("{node_id:?}"));
+        let children = view.children.get(&node_id);
+        let is_cycle = visiting.contains(&node_id);
+
+        rows.push(TreeRow {
+            node_id,
+            depth,
+            label,
+            has_children: children.is_some_and(|c| !c.is_empty()),
+            is_cycle,
+        });
+
+        if is_cycle {
+            return;
+        }
+
+        visiting.push(node_id);
+        if let Some(children) = children {
+            for &child in children {
+                self.visit(view, child, depth + 1, visiting, rows);
+            }
+        }
+        visiting.pop();
+    }
+}
+
+/// Default tree morphism, used by [`render_tree_panel`].
+#[derive(Default)]
+pub struct StandardTreeMorphism;
+
+impl TreeMorphism for StandardTreeMorphism {}
+
+/// System keeping [`TreeView`] in sync with the 3D view's own `Create*Visual`,
+/// `Remove*Visual`, and `NodeMetadataChanged` events, so the tree panel never needs to
+/// rebuild itself from a full query scan.
+pub fn sync_tree_view(
+    mut view: ResMut<TreeView>,
+    mut node_created: EventReader<CreateNodeVisual>,
+    mut node_removed: EventReader<RemoveNodeVisual>,
+    mut edge_created: EventReader<CreateEdgeVisual>,
+    mut edge_removed: EventReader<RemoveEdgeVisual>,
+    mut metadata_changed: EventReader<NodeMetadataChanged>,
+) {
+    for event in node_created.read() {
+        view.roots.push(event.node_id);
+    }
+
+    for event in node_removed.read() {
+        view.remove_node(&event.node_id);
+    }
+
+    for event in edge_created.read() {
+        view.roots.retain(|id| *id != event.target_id);
+        view.children.entry(event.source_id).or_default().push(event.target_id);
+    }
+
+    for event in edge_removed.read() {
+        if let Some(children) = view.children.get_mut(&event.source_id) {
+            children.retain(|id| *id != event.target_id);
+        }
+        let still_referenced = view.children.values().any(|children| children.contains(&event.target_id));
+        if !still_referenced && !view.roots.contains(&event.target_id) {
+            view.roots.push(event.target_id);
+        }
+    }
+
+    for event in metadata_changed.read() {
+        view.labels.entry(event.node_id).or_insert_with(|| format!("{:?}", event.node_id));
+    }
+}
+
+/// System rendering [`TreeView`] as an egui side panel of indented rows. Every row is
+/// always shown; rows cannot be collapsed. Clicking a row emits
+/// the same [`SelectionChanged`] the 3D view's click morphism would, hovering emits the
+/// same [`NodeHovered`]/[`NodeUnhovered`] the pointer-picking morphism would, and
+/// editing a row's label inline emits [`NodeMetadataChanged`] so the rename is reflected
+/// back into the graph and the 3D view stays unified with the panel.
+pub fn render_tree_panel(
+    mut contexts: bevy_egui::EguiContexts,
+    mut view: ResMut<TreeView>,
+    index: Res<GraphIndex>,
+    morphism: Local<StandardTreeMorphism>,
+    mut rename_buffer: Local<HashMap<NodeId, String>>,
+    mut last_hovered_row: Local<Option<NodeId>>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+    mut hovered: EventWriter<NodeHovered>,
+    mut unhovered: EventWriter<NodeUnhovered>,
+    mut metadata_changed: EventWriter<NodeMetadataChanged>,
+) {
+    let rows = morphism.project(&view);
+    let mut hovered_row = None;
+    let mut renamed = Vec::new();
+
+    bevy_egui::egui::SidePanel::left("tree_view_panel").show(contexts.ctx_mut(), |ui| {
+        for row in &rows {
+            ui.horizontal(|ui| {
+                ui.add_space(row.depth as f32 * 12.0);
+
+                let buffer = rename_buffer.entry(row.node_id).or_insert_with(|| row.label.clone());
+                let response = ui.text_edit_singleline(buffer);
+                if response.lost_focus() && *buffer != row.label {
+                    renamed.push((row.node_id, buffer.clone()));
+                }
+
+                if row.is_cycle {
+                    ui.label("(cycle)");
+                }
+
+                let row_response = ui.interact(response.rect, response.id.with("row"), bevy_egui::egui::Sense::click());
+                let Some(entity) = index.node_entity(&row.node_id) else {
+                    return;
+                };
+
+                if row_response.clicked() {
+                    selection_changed.send(SelectionChanged { entity, world_pos: Vec3::ZERO });
+                }
+                if row_response.hovered() {
+                    hovered_row = Some(row.node_id);
+                }
+            });
+        }
+    });
+
+    for (node_id, new_label) in renamed {
+        view.labels.insert(node_id, new_label);
+        metadata_changed.send(NodeMetadataChanged { node_id });
+    }
+
+    if hovered_row != *last_hovered_row {
+        if let Some(entity) = last_hovered_row.and_then(|node_id| index.node_entity(&node_id)) {
+            unhovered.send(NodeUnhovered { entity });
+        }
+        if let Some(entity) = hovered_row.and_then(|node_id| index.node_entity(&node_id)) {
+            hovered.send(NodeHovered { entity });
+        }
+    }
+    *last_hovered_row = hovered_row;
+
+    // Nodes that have since been removed no longer appear in `rows`, so their rename
+    // buffers would otherwise accumulate forever across a long session.
+    rename_buffer.retain(|node_id, _| rows.iter().any(|row| row.node_id == *node_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graph_index_survives_node_create_delete_rename_storm() {
+        let mut index = GraphIndex::default();
+        let ids: Vec<NodeId> = (0..20).map(|_| NodeId::new()).collect();
+
+        for (i, id) in ids.iter().enumerate() {
+            index.insert_node(*id, Entity::from_raw(i as u32));
+        }
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(index.node_entity(id), Some(Entity::from_raw(i as u32)));
+        }
+
+        // Delete every other node.
+        for id in ids.iter().step_by(2) {
+            assert!(index.remove_node(id).is_some());
+        }
+        for (i, id) in ids.iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(index.node_entity(id), None, "deleted node should be gone from the index");
+            } else {
+                assert_eq!(index.node_entity(id), Some(Entity::from_raw(i as u32)));
+            }
+        }
+
+        // "Rename" each survivor: drop its old id and re-insert the same entity under a
+        // fresh one, as a rename-in-place morphism would.
+        let mut renamed = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            if i % 2 == 0 {
+                continue;
+            }
+            let entity = index.remove_node(id).expect("survivor should still be indexed");
+            let new_id = NodeId::new();
+            index.insert_node(new_id, entity);
+            renamed.push((new_id, entity));
+        }
+
+        for id in &ids {
+            assert_eq!(index.node_entity(id), None, "old id must not resolve after delete or rename");
+        }
+        for (new_id, entity) in renamed {
+            assert_eq!(index.node_entity(&new_id), Some(entity));
+        }
+    }
+
+    #[test]
+    fn graph_index_survives_edge_create_delete_rename_storm() {
+        let mut index = GraphIndex::default();
+        let ids: Vec<EdgeId> = (0..20).map(|_| EdgeId::new()).collect();
+
+        for (i, id) in ids.iter().enumerate() {
+            index.insert_edge(*id, Entity::from_raw(i as u32));
+        }
+        for id in ids.iter().step_by(3) {
+            assert!(index.remove_edge(id).is_some());
+        }
+        for (i, id) in ids.iter().enumerate() {
+            if i % 3 == 0 {
+                assert_eq!(index.edge_entity(id), None);
+            } else {
+                assert_eq!(index.edge_entity(id), Some(Entity::from_raw(i as u32)));
+            }
+        }
+
+        let mut renamed = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            if i % 3 == 0 {
+                continue;
+            }
+            let entity = index.remove_edge(id).expect("survivor should still be indexed");
+            let new_id = EdgeId::new();
+            index.insert_edge(new_id, entity);
+            renamed.push((new_id, entity));
+        }
+
+        for id in &ids {
+            assert_eq!(index.edge_entity(id), None, "old id must not resolve after delete or rename");
+        }
+        for (new_id, entity) in renamed {
+            assert_eq!(index.edge_entity(&new_id), Some(entity));
+        }
+    }
+
+    #[test]
+    fn undo_replay_marker_keeps_redo_stack_intact() {
+        let mut history = MorphismHistory::default();
+        let node_id = NodeId::new();
+        let graph_id = GraphId::new();
+        let position = Vec3::new(1.0, 2.0, 3.0);
+
+        history.record(MorphismRecord::CreateNode { node_id, graph_id, position });
+
+        let index = GraphIndex::default();
+        let HistoryOutcome::Applied(records) = history.undo(&index) else {
+            panic!("expected undo to apply");
+        };
+        assert_eq!(records.len(), 1);
+        assert!(
+            matches!(records[0], MorphismRecord::DeleteNode { node_id: id, .. } if id == node_id),
+            "undoing a CreateNode must replay a DeleteNode, got {:?}", records[0]
+        );
+        for record in &records {
+            history.mark_replaying(record.touches());
+        }
+
+        // `record_morphism_history` observing the replayed `RemoveNodeVisual` must consume
+        // the marker instead of recording a fresh `DeleteNode` (which would otherwise clear
+        // the redo stack right back out).
+        assert!(history.take_replaying(GraphElementId::Node(node_id)));
+        assert!(history.applied.is_empty());
+        assert_eq!(history.undone.len(), 1);
+
+        let HistoryOutcome::Applied(redo_records) = history.redo() else {
+            panic!("expected redo to apply");
+        };
+        assert_eq!(redo_records.len(), 1);
+        assert!(
+            matches!(redo_records[0], MorphismRecord::CreateNode { node_id: id, graph_id: gid, position: pos }
+                if id == node_id && gid == graph_id && pos == position),
+            "redoing an undone CreateNode must replay the original CreateNode, not its inverse; got {:?}",
+            redo_records[0]
+        );
+        assert_eq!(history.applied.len(), 1);
+    }
+
+    #[test]
+    fn repulsion_direction_jitters_coincident_nodes_instead_of_nan() {
+        let id_a = NodeId::new();
+        let id_b = NodeId::new();
+
+        let direction = repulsion_direction(Vec3::ZERO, id_a, id_b);
+
+        assert!(direction.is_finite(), "degenerate overlap must not produce NaN");
+        assert!((direction.length() - 1.0).abs() < 1e-4);
+        // Same pair of ids always jitters the same way.
+        assert_eq!(direction, repulsion_direction(Vec3::ZERO, id_a, id_b));
+    }
+
+    #[test]
+    fn layout_temperature_cools_linearly_toward_zero() {
+        let config = LayoutConfig { area: 1_000.0, constant: 1.0, iterations: 10, max_displacement: 10.0 };
+
+        assert_eq!(cooled_temperature(&config, 0), 10.0);
+        assert_eq!(cooled_temperature(&config, 5), 5.0);
+        assert_eq!(cooled_temperature(&config, 9), 1.0);
+
+        let mut previous = cooled_temperature(&config, 0);
+        for iteration in 1..config.iterations {
+            let current = cooled_temperature(&config, iteration);
+            assert!(current < previous, "temperature must strictly decrease");
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn find_route_detours_around_a_blocked_cell() {
+        let config = EdgeRoutingConfig {
+            mode: EdgeRoutingMode::Routed,
+            cell_size: 1.0,
+            node_radius: 0.5,
+            turn_penalty: 1.0,
+        };
+        let start = Vec3::new(-3.0, 0.0, 0.0);
+        let end = Vec3::new(3.0, 0.0, 0.0);
+        let blockers = [Vec3::ZERO];
+
+        let waypoints = find_route(start, end, &blockers, &config);
+
+        assert_eq!(*waypoints.first().unwrap(), start);
+        assert_eq!(*waypoints.last().unwrap(), end);
+        assert!(waypoints.len() > 2, "a direct line is blocked, so the route must detour");
+        for waypoint in &waypoints[1..waypoints.len() - 1] {
+            assert!(
+                waypoint.x.abs() > 1.0 || waypoint.y.abs() > 1.0,
+                "waypoint {waypoint:?} falls inside the blocked radius around the origin"
+            );
+        }
+    }
+
+    #[test]
+    fn find_route_falls_back_to_direct_line_when_unreachable() {
+        let config = EdgeRoutingConfig {
+            mode: EdgeRoutingMode::Routed,
+            cell_size: 1.0,
+            node_radius: 50.0,
+            turn_penalty: 1.0,
+        };
+        let start = Vec3::new(-3.0, 0.0, 0.0);
+        let end = Vec3::new(3.0, 0.0, 0.0);
+        let blockers = [Vec3::ZERO];
+
+        let waypoints = find_route(start, end, &blockers, &config);
+
+        assert_eq!(waypoints, vec![start, end]);
+    }
+}